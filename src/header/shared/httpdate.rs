@@ -1,9 +1,7 @@
-use std::fmt::{self, Display};
+use std::fmt::{self, Display, Write};
 use std::str::FromStr;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use time;
-
 /// A timestamp with HTTP formatting and parsing
 //   Prior to 1995, there were three different formats commonly used by
 //   servers to communicate timestamps.  For compatibility with old
@@ -28,72 +26,418 @@ use time;
 //   HTTP-date, the sender MUST generate those timestamps in the
 //   IMF-fixdate format.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
-pub struct HttpDate(time::Tm);
+pub struct HttpDate {
+    /// 0...60 (60 only for a parsed leap second)
+    sec: u8,
+    /// 0...59
+    min: u8,
+    /// 0...23
+    hour: u8,
+    /// 1...31
+    day: u8,
+    /// 1...12
+    mon: u8,
+    /// 1970...9999
+    year: u16,
+    /// 0...6, 0 is Sunday
+    wday: u8,
+}
+
+// Days since 1970-01-01 <-> proleptic Gregorian civil date, using Howard
+// Hinnant's `civil_from_days`/`days_from_civil` algorithm. This keeps the
+// calendar math allocation-free and independent of any calendar crate.
+fn civil_from_days(days: i64) -> (i64, u8, u8) {
+    let z = days + 719468;
+    let era = if z >= 0 { z } else { z - 146096 } / 146097;
+    let doe = (z - era * 146097) as u64; // [0, 146096]
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365; // [0, 399]
+    let y = yoe as i64 + era * 400;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100); // [0, 365]
+    let mp = (5 * doy + 2) / 153; // [0, 11]
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u8; // [1, 31]
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u8; // [1, 12]
+    (if m <= 2 { y + 1 } else { y }, m, d)
+}
+
+fn days_from_civil(year: i64, mon: u8, day: u8) -> i64 {
+    let y = if mon <= 2 { year - 1 } else { year };
+    let era = if y >= 0 { y } else { y - 399 } / 400;
+    let yoe = (y - era * 400) as u64; // [0, 399]
+    let mon = mon as u64;
+    let doy = (153 * (if mon > 2 { mon - 3 } else { mon + 9 }) + 2) / 5 + day as u64 - 1; // [0, 365]
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy; // [0, 146096]
+    era * 146097 + doe as i64 - 719468
+}
+
+fn weekday_from_days(days: i64) -> u8 {
+    // 1970-01-01 was a Thursday.
+    (days + 4).rem_euclid(7) as u8
+}
+
+const WDAY_NAME: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+const MONTH_NAME: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+const WDAY_NAME_LONG: [&str; 7] = [
+    "Sunday", "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday",
+];
+
+// 10000-01-01T00:00:00Z, the first instant after the end of year 9999:
+// dates on or past this instant don't fit in a 4-digit year and are rejected.
+const YEAR_9999: i64 = 253_402_300_800;
+
+fn wday_from_name(s: &str) -> Option<u8> {
+    WDAY_NAME.iter().position(|&n| n == s).map(|i| i as u8)
+}
+
+fn wday_from_long_name(s: &str) -> Option<u8> {
+    WDAY_NAME_LONG.iter().position(|&n| n == s).map(|i| i as u8)
+}
+
+fn month_from_name(s: &str) -> Option<u8> {
+    MONTH_NAME.iter().position(|&n| n == s).map(|i| i as u8 + 1)
+}
+
+fn digit(b: u8) -> Option<u8> {
+    if b.is_ascii_digit() {
+        Some(b - b'0')
+    } else {
+        None
+    }
+}
+
+// Parses two ASCII digits, e.g. b"06" -> 6.
+fn two_digits(b: &[u8]) -> Option<u8> {
+    Some(digit(b[0])? * 10 + digit(b[1])?)
+}
+
+// Parses a day-of-month where a leading space stands for a suppressed
+// leading zero, as asctime's `%e` does: b" 6" -> 6, b"16" -> 16.
+fn space_padded_day(b0: u8, b1: u8) -> Option<u8> {
+    let ones = digit(b1)?;
+    let tens = if b0 == b' ' { 0 } else { digit(b0)? };
+    Some(tens * 10 + ones)
+}
+
+fn four_digits(b: &[u8]) -> Option<u16> {
+    let mut year = 0u16;
+    for &c in b {
+        year = year * 10 + digit(c)? as u16;
+    }
+    Some(year)
+}
+
+// Validates field ranges and the overall date, then returns the
+// corresponding count of seconds since the epoch. Catches both
+// out-of-range fields (`day: 32`) and instants outside 1970...9999 (a
+// year before 1970 or after 9999). The day is not checked against the
+// length of its month, so `31 Feb` passes. Shared by every format that
+// parses a civil date, so the range checks live in exactly one place.
+fn civil_seconds(year: u16, mon: u8, day: u8, hour: u8, min: u8, sec: u8) -> Option<i64> {
+    if !(1..=12).contains(&mon) || !(1..=31).contains(&day) || hour > 23 || min > 59 || sec > 60 {
+        return None;
+    }
+    let days = days_from_civil(year as i64, mon, day);
+    let secs = days * 86400 + hour as i64 * 3600 + min as i64 * 60 + sec as i64;
+    if !(0..YEAR_9999).contains(&secs) {
+        return None;
+    }
+    Some(secs)
+}
+
+// Validates field ranges and the overall date, then assembles an `HttpDate`.
+fn build(wday: u8, day: u8, mon: u8, year: u16, hour: u8, min: u8, sec: u8) -> Option<HttpDate> {
+    civil_seconds(year, mon, day, hour, min, sec)?;
+    Some(HttpDate {
+        sec,
+        min,
+        hour,
+        day,
+        mon,
+        year,
+        wday,
+    })
+}
+
+// "Sun, 06 Nov 1994 08:49:37 GMT", always 29 bytes.
+fn parse_imf_fixdate(s: &[u8]) -> Option<HttpDate> {
+    if s[3] != b',' || s[4] != b' ' || s[7] != b' ' || s[11] != b' ' || s[16] != b' '
+        || s[19] != b':' || s[22] != b':' || s[25] != b' ' || &s[26..29] != b"GMT"
+    {
+        return None;
+    }
+    let wday = wday_from_name(str::from_utf8(&s[0..3]).ok()?)?;
+    let day = two_digits(&s[5..7])?;
+    let mon = month_from_name(str::from_utf8(&s[8..11]).ok()?)?;
+    let year = four_digits(&s[12..16])?;
+    let hour = two_digits(&s[17..19])?;
+    let min = two_digits(&s[20..22])?;
+    let sec = two_digits(&s[23..25])?;
+    build(wday, day, mon, year, hour, min, sec)
+}
+
+// "Sunday, 06-Nov-94 08:49:37 GMT". The weekday name's length varies, but
+// everything from the day-of-month on is a fixed 22-byte tail.
+fn parse_rfc850(s: &[u8]) -> Option<HttpDate> {
+    let comma = s.iter().position(|&b| b == b',')?;
+    let wday = wday_from_long_name(str::from_utf8(&s[..comma]).ok()?)?;
+    if s.get(comma + 1) != Some(&b' ') {
+        return None;
+    }
+    let tail = &s[comma + 2..];
+    if tail.len() != 22
+        || tail[2] != b'-' || tail[6] != b'-' || tail[9] != b' '
+        || tail[12] != b':' || tail[15] != b':' || tail[18] != b' '
+        || &tail[19..22] != b"GMT"
+    {
+        return None;
+    }
+    let day = two_digits(&tail[0..2])?;
+    let mon = month_from_name(str::from_utf8(&tail[3..6]).ok()?)?;
+    let yy = two_digits(&tail[7..9])?;
+    // RFC 850 two-digit years use a fixed pivot: 00-69 is taken as
+    // 2000-2069 and 70-99 as 1970-1999.
+    let year = if yy < 70 { 2000 + yy as u16 } else { 1900 + yy as u16 };
+    let hour = two_digits(&tail[10..12])?;
+    let min = two_digits(&tail[13..15])?;
+    let sec = two_digits(&tail[16..18])?;
+    build(wday, day, mon, year, hour, min, sec)
+}
+
+// "Sun Nov  6 08:49:37 1994", always 24 bytes.
+fn parse_asctime(s: &[u8]) -> Option<HttpDate> {
+    if s[3] != b' ' || s[7] != b' ' || s[10] != b' ' || s[13] != b':' || s[16] != b':'
+        || s[19] != b' '
+    {
+        return None;
+    }
+    let wday = wday_from_name(str::from_utf8(&s[0..3]).ok()?)?;
+    let mon = month_from_name(str::from_utf8(&s[4..7]).ok()?)?;
+    let day = space_padded_day(s[8], s[9])?;
+    let hour = two_digits(&s[11..13])?;
+    let min = two_digits(&s[14..16])?;
+    let sec = two_digits(&s[17..19])?;
+    let year = four_digits(&s[20..24])?;
+    build(wday, day, mon, year, hour, min, sec)
+}
 
 impl FromStr for HttpDate {
     type Err = ::Error;
     fn from_str(s: &str) -> ::Result<HttpDate> {
-        match time::strptime(s, "%a, %d %b %Y %T %Z").or_else(|_| {
-            time::strptime(s, "%A, %d-%b-%y %T %Z")
-            }).or_else(|_| {
-                time::strptime(s, "%c")
-                }) {
-                    Ok(t) => Ok(HttpDate(t)),
-                    Err(_) => Err(::Error::Header),
-                    }
+        let parsed = if !s.is_ascii() {
+            None
+        } else if s.len() == 29 {
+            parse_imf_fixdate(s.as_bytes())
+        } else if s.len() == 24 {
+            parse_asctime(s.as_bytes())
+        } else {
+            parse_rfc850(s.as_bytes())
+        };
+        parsed.ok_or(::Error::Header)
     }
 }
 
 impl Display for HttpDate {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        fmt::Display::fmt(&self.0.to_utc().rfc822(), f)
+        write!(
+            f,
+            "{}, {:02} {} {:04} {:02}:{:02}:{:02} GMT",
+            WDAY_NAME[self.wday as usize],
+            self.day,
+            MONTH_NAME[self.mon as usize - 1],
+            self.year,
+            self.hour,
+            self.min,
+            self.sec,
+        )
     }
 }
 
 impl From<SystemTime> for HttpDate {
     fn from(sys: SystemTime) -> HttpDate {
-        let tmspec = match sys.duration_since(UNIX_EPOCH) {
-            Ok(dur) => {
-                time::Timespec::new(dur.as_secs() as i64, dur.subsec_nanos() as i32)
-            },
-            Err(err) => {
-                let neg = err.duration();
-                time::Timespec::new(-(neg.as_secs() as i64), -(neg.subsec_nanos() as i32))
-            },
-        };
-        HttpDate(time::at_utc(tmspec))
+        // `HttpDate`'s year field only covers 1970...9999, so a `SystemTime`
+        // before the epoch is saturated to it rather than panicking.
+        let secs = sys.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let days = (secs / 86400) as i64;
+        let rem = secs % 86400;
+
+        let (year, mon, day) = civil_from_days(days);
+
+        HttpDate {
+            sec: (rem % 60) as u8,
+            min: ((rem / 60) % 60) as u8,
+            hour: (rem / 3600) as u8,
+            day,
+            mon,
+            year: year as u16,
+            wday: weekday_from_days(days),
+        }
     }
 }
 
 impl From<HttpDate> for SystemTime {
     fn from(date: HttpDate) -> SystemTime {
-        let spec = date.0.to_timespec();
-        if spec.sec >= 0 {
-            UNIX_EPOCH + Duration::new(spec.sec as u64, spec.nsec as u32)
-        } else {
-            UNIX_EPOCH - Duration::new(spec.sec as u64, spec.nsec as u32)
+        let days = days_from_civil(date.year as i64, date.mon, date.day);
+        let secs = days * 86400
+            + date.hour as i64 * 3600
+            + date.min as i64 * 60
+            + date.sec as i64;
+        UNIX_EPOCH + Duration::new(secs as u64, 0)
+    }
+}
+
+/// Parse an HTTP-date, in any of the three formats permitted by RFC 7231
+/// (IMF-fixdate, RFC 850, or asctime), directly into a `SystemTime`.
+///
+/// This is a convenience over constructing an [`HttpDate`] and converting
+/// it, for callers that only need the resulting timestamp.
+pub fn parse_http_date(s: &str) -> ::Result<SystemTime> {
+    s.parse::<HttpDate>().map(SystemTime::from)
+}
+
+/// Format a `SystemTime` as an IMF-fixdate, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`.
+pub fn fmt_http_date(d: SystemTime) -> String {
+    let mut s = String::with_capacity(29);
+    write_http_date(&mut s, d).expect("writing to a String never fails");
+    s
+}
+
+/// Write a `SystemTime`, formatted as an IMF-fixdate, into any `fmt::Write`.
+///
+/// This avoids the intermediate `String` allocation made by
+/// [`fmt_http_date`] when the caller already has a buffer to write into.
+pub fn write_http_date<W: Write>(w: &mut W, d: SystemTime) -> fmt::Result {
+    write!(w, "{}", HttpDate::from(d))
+}
+
+/// Selects which textual representation a [`Timestamp`] is parsed from or
+/// rendered as.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimestampFormat {
+    /// The HTTP `Date:` header format, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`.
+    /// A thin alias over [`HttpDate`] for backward compatibility.
+    HttpDate,
+    /// RFC 3339, e.g. `2015-05-15T15:34:21Z`.
+    Rfc3339,
+    /// Whole seconds since the Unix epoch, e.g. `1431703261`.
+    EpochSeconds,
+}
+
+/// A timestamp that can be parsed from, and rendered as, any of the
+/// formats in [`TimestampFormat`]: the HTTP `Date:` header as well as the
+/// RFC 3339 and epoch-seconds formats used elsewhere in HTTP (JSON bodies,
+/// signed-expiry query parameters, and the like). The HTTP-date and RFC 3339
+/// formats share the same allocation-free civil-calendar core as `HttpDate`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Timestamp(SystemTime);
+
+impl Timestamp {
+    /// Parses `s` as a timestamp in the given format.
+    pub fn parse(s: &str, format: TimestampFormat) -> ::Result<Timestamp> {
+        match format {
+            TimestampFormat::HttpDate => parse_http_date(s),
+            TimestampFormat::Rfc3339 => parse_rfc3339(s),
+            TimestampFormat::EpochSeconds => parse_epoch_seconds(s),
+        }
+        .map(Timestamp)
+    }
+
+    /// Renders this timestamp in the given format.
+    pub fn format(self, format: TimestampFormat) -> String {
+        match format {
+            TimestampFormat::HttpDate => fmt_http_date(self.0),
+            TimestampFormat::Rfc3339 => fmt_rfc3339(self.0),
+            TimestampFormat::EpochSeconds => fmt_epoch_seconds(self.0),
         }
     }
 }
 
+impl From<SystemTime> for Timestamp {
+    fn from(t: SystemTime) -> Timestamp {
+        Timestamp(t)
+    }
+}
+
+impl From<Timestamp> for SystemTime {
+    fn from(t: Timestamp) -> SystemTime {
+        t.0
+    }
+}
+
+impl From<HttpDate> for Timestamp {
+    fn from(d: HttpDate) -> Timestamp {
+        Timestamp(d.into())
+    }
+}
+
+// "2015-05-15T15:34:21Z", always 20 bytes. RFC 3339 allows fractional
+// seconds and non-Z offsets, but HTTP only ever needs the UTC instant.
+fn parse_rfc3339_secs(s: &[u8]) -> Option<i64> {
+    if s[4] != b'-' || s[7] != b'-' || s[10] != b'T' || s[13] != b':' || s[16] != b':'
+        || s[19] != b'Z'
+    {
+        return None;
+    }
+    let year = four_digits(&s[0..4])?;
+    let mon = two_digits(&s[5..7])?;
+    let day = two_digits(&s[8..10])?;
+    let hour = two_digits(&s[11..13])?;
+    let min = two_digits(&s[14..16])?;
+    let sec = two_digits(&s[17..19])?;
+    civil_seconds(year, mon, day, hour, min, sec)
+}
+
+fn parse_rfc3339(s: &str) -> ::Result<SystemTime> {
+    if !s.is_ascii() || s.len() != 20 {
+        return Err(::Error::Header);
+    }
+    parse_rfc3339_secs(s.as_bytes())
+        .map(|secs| UNIX_EPOCH + Duration::new(secs as u64, 0))
+        .ok_or(::Error::Header)
+}
+
+fn fmt_rfc3339(t: SystemTime) -> String {
+    let HttpDate {
+        year,
+        mon,
+        day,
+        hour,
+        min,
+        sec,
+        ..
+    } = HttpDate::from(t);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year, mon, day, hour, min, sec
+    )
+}
+
+fn parse_epoch_seconds(s: &str) -> ::Result<SystemTime> {
+    let secs: u64 = s.parse().map_err(|_| ::Error::Header)?;
+    Ok(UNIX_EPOCH + Duration::new(secs, 0))
+}
+
+fn fmt_epoch_seconds(t: SystemTime) -> String {
+    // Pre-epoch instants are saturated to 0, matching `HttpDate::from`.
+    let secs = t.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    secs.to_string()
+}
+
 #[cfg(test)]
 mod tests {
-    use time::Tm;
-    use super::HttpDate;
-
-    const NOV_07: HttpDate = HttpDate(Tm {
-        tm_nsec: 0,
-        tm_sec: 37,
-        tm_min: 48,
-        tm_hour: 8,
-        tm_mday: 7,
-        tm_mon: 10,
-        tm_year: 94,
-        tm_wday: 0,
-        tm_isdst: 0,
-        tm_yday: 0,
-        tm_utcoff: 0,
-    });
+    use std::time::SystemTime;
+    use super::{fmt_http_date, parse_http_date, HttpDate, Timestamp, TimestampFormat};
+
+    const NOV_07: HttpDate = HttpDate {
+        sec: 37,
+        min: 48,
+        hour: 8,
+        day: 7,
+        mon: 11,
+        year: 1994,
+        wday: 0,
+    };
 
     #[test]
     fn test_imf_fixdate() {
@@ -114,4 +458,80 @@ mod tests {
     fn test_no_date() {
         assert!("this-is-no-date".parse::<HttpDate>().is_err());
     }
+
+    #[test]
+    fn test_roundtrip() {
+        let d = NOV_07;
+        let s = d.to_string();
+        assert_eq!(s.parse::<HttpDate>().unwrap(), d);
+    }
+
+    #[test]
+    fn test_free_functions_roundtrip() {
+        // Nov 07 1994 was a Monday; `SystemTime` round-trips recompute the
+        // weekday from the date rather than trusting a stored field.
+        let sys: SystemTime = "Mon, 07 Nov 1994 08:48:37 GMT".parse::<HttpDate>().unwrap().into();
+        assert_eq!(fmt_http_date(sys), "Mon, 07 Nov 1994 08:48:37 GMT");
+        assert_eq!(parse_http_date(&fmt_http_date(sys)).unwrap(), sys);
+    }
+
+    #[test]
+    fn test_pre_epoch_saturates_instead_of_panicking() {
+        use std::time::{Duration, UNIX_EPOCH};
+        let sys = UNIX_EPOCH - Duration::new(10, 0);
+        assert_eq!(fmt_http_date(sys), "Thu, 01 Jan 1970 00:00:00 GMT");
+    }
+
+    #[test]
+    fn test_timestamp_pre_epoch_epoch_seconds_saturates() {
+        use std::time::{Duration, UNIX_EPOCH};
+        let t: Timestamp = (UNIX_EPOCH - Duration::new(1, 0)).into();
+        assert_eq!(t.format(TimestampFormat::EpochSeconds), "0");
+    }
+
+    #[test]
+    fn test_invalid_day() {
+        assert!("Sun, 32 Nov 1994 08:48:37 GMT".parse::<HttpDate>().is_err());
+    }
+
+    #[test]
+    fn test_invalid_hour() {
+        assert!("Sun, 07 Nov 1994 24:48:37 GMT".parse::<HttpDate>().is_err());
+    }
+
+    #[test]
+    fn test_invalid_separators() {
+        assert!("Sun- 07 Nov 1994 08:48:37 GMT".parse::<HttpDate>().is_err());
+    }
+
+    #[test]
+    fn test_year_9999_is_valid() {
+        assert!("Fri, 31 Dec 9999 23:59:59 GMT".parse::<HttpDate>().is_ok());
+    }
+
+    #[test]
+    fn test_timestamp_http_date() {
+        let t = Timestamp::parse("Sun, 06 Nov 1994 08:49:37 GMT", TimestampFormat::HttpDate).unwrap();
+        assert_eq!(t.format(TimestampFormat::HttpDate), "Sun, 06 Nov 1994 08:49:37 GMT");
+    }
+
+    #[test]
+    fn test_timestamp_rfc3339_roundtrip() {
+        let t = Timestamp::parse("1994-11-06T08:49:37Z", TimestampFormat::Rfc3339).unwrap();
+        assert_eq!(t.format(TimestampFormat::Rfc3339), "1994-11-06T08:49:37Z");
+        assert_eq!(t.format(TimestampFormat::HttpDate), "Sun, 06 Nov 1994 08:49:37 GMT");
+    }
+
+    #[test]
+    fn test_timestamp_epoch_seconds_roundtrip() {
+        let t = Timestamp::parse("784111777", TimestampFormat::EpochSeconds).unwrap();
+        assert_eq!(t.format(TimestampFormat::EpochSeconds), "784111777");
+        assert_eq!(t.format(TimestampFormat::HttpDate), "Sun, 06 Nov 1994 08:49:37 GMT");
+    }
+
+    #[test]
+    fn test_timestamp_rfc3339_invalid() {
+        assert!(Timestamp::parse("1994-13-06T08:49:37Z", TimestampFormat::Rfc3339).is_err());
+        assert!(Timestamp::parse("not-a-date", TimestampFormat::Rfc3339).is_err());
+    }
 }